@@ -0,0 +1,826 @@
+//! Game Boy APU (Audio Processing Unit)
+//!
+//! Emulates the four DMG sound channels - two square waves, a programmable
+//! wave channel, and noise - mixed through NR50/NR51/NR52 into mono samples
+//! at the core's 32768 Hz output rate.
+//!
+//! Registers are read directly from the shared `io` array (NR10-NR44 at
+//! 0xFF10-0xFF23, NR50-NR52 at 0xFF24-0xFF26, wave RAM at 0xFF30-0xFF3F) so
+//! no register plumbing is needed beyond `Bus` storing CPU writes there, the
+//! same way the PPU reads its LCD registers.
+
+use crate::savestate::{StateReader, StateWriter};
+
+/// T-cycles between generated samples, for a 32768 Hz output rate.
+const CYCLES_PER_SAMPLE: u32 = 4_194_304 / 32768;
+
+// NR10 (channel 1 frequency sweep) and the NRx1 length counters on both
+// square channels are modeled below (`Sweep`, `LengthCounter`). The noise
+// and wave channels' length counters (NR41/NR31) aren't, since nothing in
+// this port currently depends on those channels timing out on their own.
+mod reg {
+    pub const NR10: usize = 0x10;
+    pub const NR11: usize = 0x11;
+    pub const NR12: usize = 0x12;
+    pub const NR13: usize = 0x13;
+    pub const NR14: usize = 0x14;
+    pub const NR21: usize = 0x16;
+    pub const NR22: usize = 0x17;
+    pub const NR23: usize = 0x18;
+    pub const NR24: usize = 0x19;
+    pub const NR30: usize = 0x1A;
+    pub const NR32: usize = 0x1C;
+    pub const NR33: usize = 0x1D;
+    pub const NR34: usize = 0x1E;
+    pub const NR42: usize = 0x21;
+    pub const NR43: usize = 0x22;
+    pub const NR44: usize = 0x23;
+    pub const NR50: usize = 0x24;
+    pub const NR51: usize = 0x25;
+    pub const WAVE_RAM: usize = 0x30;
+}
+
+/// Square wave duty cycle patterns (12.5%, 25%, 50%, 75% high)
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0],
+];
+
+/// A periodic up/down volume envelope (NRx2 on channels 1, 2 and 4): the
+/// current volume steps by one every `pace` 64 Hz ticks, in the direction
+/// given by bit 3, clamping at 0/15 rather than wrapping.
+#[derive(Default)]
+struct Envelope {
+    volume: u8,
+    timer: u32,
+}
+
+impl Envelope {
+    /// T-cycles per 64 Hz envelope tick at pace 1; higher paces are a
+    /// multiple of this.
+    const PERIOD: u32 = 4_194_304 / 64;
+
+    /// Reload the initial volume from NRx2 on a channel trigger.
+    fn trigger(&mut self, nrx2: u8) {
+        self.volume = (nrx2 >> 4) & 0x0F;
+        self.timer = 0;
+    }
+
+    /// Advance by `cycles`, stepping the volume per NRx2's pace/direction.
+    /// A pace of 0 disables the envelope, per hardware.
+    fn step(&mut self, cycles: u32, nrx2: u8) {
+        let pace = (nrx2 & 0x07) as u32;
+        if pace == 0 {
+            return;
+        }
+
+        let increasing = nrx2 & 0x08 != 0;
+        let period = Self::PERIOD * pace;
+
+        self.timer += cycles;
+        while self.timer >= period {
+            self.timer -= period;
+            if increasing {
+                self.volume = (self.volume + 1).min(15);
+            } else {
+                self.volume = self.volume.saturating_sub(1);
+            }
+        }
+    }
+
+    fn level(&self) -> f32 {
+        self.volume as f32 / 15.0
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.volume);
+        w.u32(self.timer);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.volume = r.u8()?;
+        self.timer = r.u32()?;
+        Ok(())
+    }
+}
+
+/// The NRx1 length counter (used by the two square channels): while length is
+/// enabled (NRx4 bit 6), the channel silences itself `64 - initial length`
+/// ticks after being triggered, at 256 Hz, independent of the envelope or
+/// sweep.
+#[derive(Default)]
+struct LengthCounter {
+    remaining: u16,
+    enabled: bool,
+    timer: u32,
+}
+
+impl LengthCounter {
+    /// T-cycles per 256 Hz length tick.
+    const PERIOD: u32 = 4_194_304 / 256;
+
+    /// Reload from NRx1's length bits (0-63) and NRx4's length-enable bit on
+    /// a channel trigger.
+    fn trigger(&mut self, nrx1: u8, nrx4: u8) {
+        self.remaining = 64 - (nrx1 & 0x3F) as u16;
+        self.enabled = nrx4 & 0x40 != 0;
+        self.timer = 0;
+    }
+
+    /// Advance by `cycles`, counting down towards silence while enabled.
+    fn step(&mut self, cycles: u32) {
+        if !self.enabled || self.remaining == 0 {
+            return;
+        }
+
+        self.timer += cycles;
+        while self.timer >= Self::PERIOD && self.remaining > 0 {
+            self.timer -= Self::PERIOD;
+            self.remaining -= 1;
+        }
+    }
+
+    /// Whether the channel should still be heard: either length isn't
+    /// enabled, or it hasn't yet counted down to zero.
+    fn active(&self) -> bool {
+        !self.enabled || self.remaining > 0
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.remaining);
+        w.bool(self.enabled);
+        w.u32(self.timer);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.remaining = r.u16()?;
+        self.enabled = r.bool()?;
+        self.timer = r.u32()?;
+        Ok(())
+    }
+}
+
+/// Channel 1's frequency sweep (NR10): every `pace` 128 Hz ticks, shifts the
+/// frequency by `frequency >> shift` in the direction given by bit 3,
+/// silencing the channel if the result overflows past the 11-bit frequency
+/// range. Channel 2 has no NR10 equivalent, so its `Sweep` is triggered with
+/// NR10 = 0 (which leaves it disabled) and never stepped.
+#[derive(Default)]
+struct Sweep {
+    timer: u32,
+    shadow_freq: u32,
+    enabled: bool,
+    silenced: bool,
+}
+
+impl Sweep {
+    /// T-cycles per 128 Hz sweep tick at pace 1; higher paces are a multiple
+    /// of this.
+    const PERIOD: u32 = 4_194_304 / 128;
+
+    /// Reload the shadow frequency from NR13/NR14 on a channel trigger, and
+    /// silence the channel immediately if the first shift would overflow.
+    fn trigger(&mut self, freq: u32, nr10: u8) {
+        self.shadow_freq = freq;
+        self.timer = 0;
+
+        let pace = (nr10 >> 4) & 0x07;
+        let shift = nr10 & 0x07;
+        self.enabled = pace != 0 || shift != 0;
+        self.silenced = shift != 0 && Self::shifted(self.shadow_freq, nr10) > 2047;
+    }
+
+    fn shifted(freq: u32, nr10: u8) -> u32 {
+        let delta = freq >> (nr10 & 0x07);
+        if nr10 & 0x08 != 0 {
+            freq.saturating_sub(delta)
+        } else {
+            freq + delta
+        }
+    }
+
+    /// Advance by `cycles`, updating the shadow frequency at NR10's pace.
+    fn step(&mut self, cycles: u32, nr10: u8) {
+        if self.silenced || !self.enabled {
+            return;
+        }
+
+        let pace = ((nr10 >> 4) & 0x07) as u32;
+        if pace == 0 {
+            return;
+        }
+
+        let period = Self::PERIOD * pace;
+        self.timer += cycles;
+        while self.timer >= period {
+            self.timer -= period;
+
+            let next = Self::shifted(self.shadow_freq, nr10);
+            if next > 2047 {
+                self.silenced = true;
+                break;
+            }
+            if nr10 & 0x07 != 0 {
+                self.shadow_freq = next;
+            }
+        }
+    }
+
+    /// The frequency channel 1 should currently play: the sweeping shadow
+    /// frequency while active, or `raw_freq` (straight off NR13/NR14) once
+    /// sweep has silenced the channel or was never enabled.
+    fn current(&self, raw_freq: u32) -> u32 {
+        if self.enabled && !self.silenced {
+            self.shadow_freq
+        } else {
+            raw_freq
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u32(self.timer);
+        w.u32(self.shadow_freq);
+        w.bool(self.enabled);
+        w.bool(self.silenced);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.timer = r.u32()?;
+        self.shadow_freq = r.u32()?;
+        self.enabled = r.bool()?;
+        self.silenced = r.bool()?;
+        Ok(())
+    }
+}
+
+/// A square-wave channel (channel 1 has a frequency sweep on top of this)
+#[derive(Default)]
+struct SquareChannel {
+    phase: u8,
+    freq_timer: u32,
+    prev_trigger: u8,
+    envelope: Envelope,
+    length: LengthCounter,
+    sweep: Sweep,
+}
+
+impl SquareChannel {
+    fn frequency(&self, lo: u8, hi: u8) -> u32 {
+        (((hi as u32 & 0x07) << 8) | lo as u32).min(2047)
+    }
+
+    fn period(freq: u32) -> u32 {
+        (2048 - freq) * 4
+    }
+
+    /// Advance the phase by `cycles`, returning the current duty-cycle bit.
+    fn step(&mut self, cycles: u32, duty: u8, freq: u32) -> u8 {
+        self.freq_timer += cycles;
+        let period = Self::period(freq).max(1);
+        while self.freq_timer >= period {
+            self.freq_timer -= period;
+            self.phase = (self.phase + 1) % 8;
+        }
+        DUTY_TABLE[duty as usize & 0x03][self.phase as usize]
+    }
+
+    /// `nr10` is 0 for channel 2, which has no sweep register - that makes
+    /// `Sweep::trigger` compute a disabled sweep, so it's simply never
+    /// stepped or audible.
+    fn triggered(&mut self, nrx4: u8, nrx3: u8, nrx1: u8, nrx2: u8, nr10: u8) -> bool {
+        let fired = nrx4 & 0x80 != 0 && nrx4 != self.prev_trigger;
+        self.prev_trigger = nrx4;
+        if fired {
+            self.phase = 0;
+            self.freq_timer = 0;
+            self.envelope.trigger(nrx2);
+            self.length.trigger(nrx1, nrx4);
+            let freq = self.frequency(nrx3, nrx4);
+            self.sweep.trigger(freq, nr10);
+        }
+        fired
+    }
+
+    fn step_envelope(&mut self, cycles: u32, nrx2: u8) {
+        self.envelope.step(cycles, nrx2);
+    }
+
+    fn step_length(&mut self, cycles: u32) {
+        self.length.step(cycles);
+    }
+
+    fn step_sweep(&mut self, cycles: u32, nr10: u8) {
+        self.sweep.step(cycles, nr10);
+    }
+
+    fn volume(&self) -> f32 {
+        self.envelope.level()
+    }
+
+    fn length_active(&self) -> bool {
+        self.length.active()
+    }
+
+    fn sweep_frequency(&self, raw_freq: u32) -> u32 {
+        self.sweep.current(raw_freq)
+    }
+
+    fn sweep_silenced(&self) -> bool {
+        self.sweep.silenced
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.phase);
+        w.u32(self.freq_timer);
+        w.u8(self.prev_trigger);
+        self.envelope.save_state(w);
+        self.length.save_state(w);
+        self.sweep.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.phase = r.u8()?;
+        self.freq_timer = r.u32()?;
+        self.prev_trigger = r.u8()?;
+        self.envelope.load_state(r)?;
+        self.length.load_state(r)?;
+        self.sweep.load_state(r)
+    }
+}
+
+/// The wave channel (channel 3), playing back 32 4-bit samples from wave RAM.
+#[derive(Default)]
+struct WaveChannel {
+    position: usize,
+    freq_timer: u32,
+    prev_trigger: u8,
+}
+
+impl WaveChannel {
+    fn step(&mut self, cycles: u32, freq: u32) -> usize {
+        self.freq_timer += cycles;
+        let period = ((2048 - freq) * 2).max(1);
+        while self.freq_timer >= period {
+            self.freq_timer -= period;
+            self.position = (self.position + 1) % 32;
+        }
+        self.position
+    }
+
+    fn triggered(&mut self, trigger_byte: u8) -> bool {
+        let fired = trigger_byte & 0x80 != 0 && trigger_byte != self.prev_trigger;
+        self.prev_trigger = trigger_byte;
+        if fired {
+            self.position = 0;
+            self.freq_timer = 0;
+        }
+        fired
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.position as u8);
+        w.u32(self.freq_timer);
+        w.u8(self.prev_trigger);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.position = r.u8()? as usize;
+        self.freq_timer = r.u32()?;
+        self.prev_trigger = r.u8()?;
+        Ok(())
+    }
+}
+
+/// The noise channel (channel 4), driven by a Linear Feedback Shift Register.
+struct NoiseChannel {
+    lfsr: u16,
+    freq_timer: u32,
+    prev_trigger: u8,
+    envelope: Envelope,
+}
+
+impl Default for NoiseChannel {
+    fn default() -> Self {
+        NoiseChannel {
+            lfsr: 0x7FFF,
+            freq_timer: 0,
+            prev_trigger: 0,
+            envelope: Envelope::default(),
+        }
+    }
+}
+
+impl NoiseChannel {
+    const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+    /// Advance the LFSR by `cycles`, returning the current output bit (0 or 1).
+    fn step(&mut self, cycles: u32, nr43: u8) -> u8 {
+        let shift = (nr43 >> 4) & 0x0F;
+        let divisor = Self::DIVISORS[(nr43 & 0x07) as usize];
+        let period = (divisor << shift).max(1);
+
+        self.freq_timer += cycles;
+        while self.freq_timer >= period {
+            self.freq_timer -= period;
+
+            let bit = (self.lfsr & 1) ^ ((self.lfsr >> 1) & 1);
+            self.lfsr >>= 1;
+            self.lfsr |= bit << 14;
+
+            if nr43 & 0x08 != 0 {
+                self.lfsr &= !(1 << 6);
+                self.lfsr |= bit << 6;
+            }
+        }
+
+        (!self.lfsr & 1) as u8
+    }
+
+    fn triggered(&mut self, trigger_byte: u8, nrx2: u8) -> bool {
+        let fired = trigger_byte & 0x80 != 0 && trigger_byte != self.prev_trigger;
+        self.prev_trigger = trigger_byte;
+        if fired {
+            self.lfsr = 0x7FFF;
+            self.envelope.trigger(nrx2);
+        }
+        fired
+    }
+
+    fn step_envelope(&mut self, cycles: u32, nrx2: u8) {
+        self.envelope.step(cycles, nrx2);
+    }
+
+    fn volume(&self) -> f32 {
+        self.envelope.level()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.lfsr);
+        w.u32(self.freq_timer);
+        w.u8(self.prev_trigger);
+        self.envelope.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.lfsr = r.u16()?;
+        self.freq_timer = r.u32()?;
+        self.prev_trigger = r.u8()?;
+        self.envelope.load_state(r)
+    }
+}
+
+/// The four-channel DMG audio mixer.
+pub struct Apu {
+    square1: SquareChannel,
+    square2: SquareChannel,
+    wave: WaveChannel,
+    noise: NoiseChannel,
+    wave_ram: [u8; 32], // unpacked nibbles, rebuilt from the packed io bytes
+    sample_counter: u32,
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Apu {
+            square1: SquareChannel::default(),
+            square2: SquareChannel::default(),
+            wave: WaveChannel::default(),
+            noise: NoiseChannel::default(),
+            wave_ram: [0; 32],
+            sample_counter: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Serialize each channel's internal phase/timer state. `wave_ram` isn't
+    /// included since it's rebuilt from the (separately-saved) `io` array by
+    /// `sync_wave_ram()` on the next `step()` call.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        self.square1.save_state(w);
+        self.square2.save_state(w);
+        self.wave.save_state(w);
+        self.noise.save_state(w);
+        w.u32(self.sample_counter);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.square1.load_state(r)?;
+        self.square2.load_state(r)?;
+        self.wave.load_state(r)?;
+        self.noise.load_state(r)?;
+        self.sample_counter = r.u32()?;
+        Ok(())
+    }
+
+    /// Rebuild the wave channel's 32-sample waveform from the packed wave-RAM
+    /// register bytes (0xFF30-0xFF3F), splitting each byte into its high and
+    /// low nibble. Called at the start of every `step()`, so channel 3 also
+    /// replays correctly after init/reset.
+    pub fn sync_wave_ram(&mut self, io: &[u8; 128]) {
+        for i in 0..16 {
+            let byte = io[reg::WAVE_RAM + i];
+            self.wave_ram[i * 2] = byte >> 4;
+            self.wave_ram[i * 2 + 1] = byte & 0x0F;
+        }
+    }
+
+    /// Advance the APU by `cycles` T-cycles, pushing any newly generated
+    /// samples (at 32768 Hz) onto `audio_buffer`.
+    pub fn step(&mut self, cycles: u32, io: &[u8; 128], audio_buffer: &mut Vec<f32>) {
+        self.sync_wave_ram(io);
+
+        let _ = self.square1.triggered(
+            io[reg::NR14],
+            io[reg::NR13],
+            io[reg::NR11],
+            io[reg::NR12],
+            io[reg::NR10],
+        );
+        let _ = self.square2.triggered(io[reg::NR24], io[reg::NR23], io[reg::NR21], io[reg::NR22], 0);
+        let _ = self.wave.triggered(io[reg::NR34]);
+        let _ = self.noise.triggered(io[reg::NR44], io[reg::NR42]);
+
+        self.square1.step_envelope(cycles, io[reg::NR12]);
+        self.square2.step_envelope(cycles, io[reg::NR22]);
+        self.noise.step_envelope(cycles, io[reg::NR42]);
+
+        self.square1.step_length(cycles);
+        self.square2.step_length(cycles);
+        self.square1.step_sweep(cycles, io[reg::NR10]);
+
+        self.sample_counter += cycles;
+        while self.sample_counter >= CYCLES_PER_SAMPLE {
+            self.sample_counter -= CYCLES_PER_SAMPLE;
+            audio_buffer.push(self.mix(io));
+        }
+    }
+
+    /// Advance every channel by one sample period's worth of cycles and mix
+    /// them down to a single [-1.0, 1.0] sample via NR50/NR51.
+    fn mix(&mut self, io: &[u8; 128]) -> f32 {
+        let power = io[0x26] & 0x80 != 0;
+        if !power {
+            return 0.0;
+        }
+
+        let raw_freq1 = self.square1.frequency(io[reg::NR13], io[reg::NR14]);
+        let freq1 = self.square1.sweep_frequency(raw_freq1);
+        let duty1 = (io[reg::NR11] >> 6) & 0x03;
+        let mut out1 = self.square1.step(CYCLES_PER_SAMPLE, duty1, freq1) as f32 * self.square1.volume();
+        if !self.square1.length_active() || self.square1.sweep_silenced() {
+            out1 = 0.0;
+        }
+
+        let freq2 = self.square2.frequency(io[reg::NR23], io[reg::NR24]);
+        let duty2 = (io[reg::NR21] >> 6) & 0x03;
+        let mut out2 = self.square2.step(CYCLES_PER_SAMPLE, duty2, freq2) as f32 * self.square2.volume();
+        if !self.square2.length_active() {
+            out2 = 0.0;
+        }
+
+        let wave_dac_on = io[reg::NR30] & 0x80 != 0;
+        let freq3 = self.wave.frequency(io[reg::NR33], io[reg::NR34]);
+        let position = self.wave.step(CYCLES_PER_SAMPLE, freq3);
+        let wave_shift = match (io[reg::NR32] >> 5) & 0x03 {
+            0 => 4, // mute
+            1 => 0,
+            2 => 1,
+            3 => 2,
+            _ => unreachable!(),
+        };
+        let out3 = if wave_dac_on {
+            ((self.wave_ram[position] >> wave_shift) as f32) / 15.0
+        } else {
+            0.0
+        };
+
+        let out4 = self.noise.step(CYCLES_PER_SAMPLE, io[reg::NR43]) as f32 * self.noise.volume();
+
+        let nr51 = io[reg::NR51];
+        let left = self.channel_sum(nr51, 0x10, out1, out2, out3, out4);
+        let right = self.channel_sum(nr51, 0x01, out1, out2, out3, out4);
+
+        let nr50 = io[reg::NR50];
+        let left_vol = ((nr50 >> 4) & 0x07) as f32 / 7.0;
+        let right_vol = (nr50 & 0x07) as f32 / 7.0;
+
+        // Mixed down to mono for the single-channel `audio_buffer`.
+        ((left * left_vol) + (right * right_vol)) / 2.0
+    }
+
+    fn channel_sum(&self, nr51: u8, side_shift: u8, out1: f32, out2: f32, out3: f32, out4: f32) -> f32 {
+        let mut sum = 0.0;
+        if nr51 & side_shift != 0 {
+            sum += out1;
+        }
+        if nr51 & (side_shift << 1) != 0 {
+            sum += out2;
+        }
+        if nr51 & (side_shift << 2) != 0 {
+            sum += out3;
+        }
+        if nr51 & (side_shift << 3) != 0 {
+            sum += out4;
+        }
+        sum / 4.0
+    }
+}
+
+impl WaveChannel {
+    fn frequency(&self, lo: u8, hi: u8) -> u32 {
+        (((hi as u32 & 0x07) << 8) | lo as u32).min(2047)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers() -> [u8; 128] {
+        let mut io = [0u8; 128];
+        io[0x26] = 0x80; // NR52: power on
+        io[reg::NR50] = 0x77; // max volume both sides
+        io[reg::NR51] = 0xFF; // all channels to both sides
+        io
+    }
+
+    #[test]
+    fn test_generates_samples_at_32768hz() {
+        let mut apu = Apu::new();
+        let io = registers();
+        let mut buffer = Vec::new();
+
+        apu.step(CYCLES_PER_SAMPLE * 10, &io, &mut buffer);
+
+        assert_eq!(buffer.len(), 10);
+    }
+
+    #[test]
+    fn test_wave_ram_restored_from_packed_bytes() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[reg::WAVE_RAM] = 0xAB;
+
+        apu.sync_wave_ram(&io);
+
+        assert_eq!(apu.wave_ram[0], 0xA);
+        assert_eq!(apu.wave_ram[1], 0xB);
+    }
+
+    #[test]
+    fn test_wave_channel_replays_after_reset() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[reg::WAVE_RAM] = 0xAB;
+        apu.sync_wave_ram(&io);
+
+        apu.reset();
+        apu.sync_wave_ram(&io);
+
+        assert_eq!(apu.wave_ram[0], 0xA);
+        assert_eq!(apu.wave_ram[1], 0xB);
+    }
+
+    #[test]
+    fn test_power_off_produces_silence() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[0x26] = 0x00; // power off
+        io[reg::NR11] = 0xC0; // 75% duty, non-zero
+        io[reg::NR12] = 0xF0; // max envelope volume
+
+        let mut buffer = Vec::new();
+        apu.step(CYCLES_PER_SAMPLE * 4, &io, &mut buffer);
+
+        assert!(buffer.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_envelope_decays_over_time() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[reg::NR11] = 0xC0; // 75% duty
+        io[reg::NR12] = 0xF1; // initial volume 15, decreasing, pace 1
+        io[reg::NR14] = 0x80; // trigger channel 1
+
+        let mut buffer = Vec::new();
+        apu.step(1, &io, &mut buffer); // trigger fires, envelope loads volume 15
+        assert_eq!(apu.square1.envelope.volume, 15);
+
+        // One pace-1 envelope tick is 4_194_304 / 64 T-cycles.
+        apu.step(Envelope::PERIOD, &io, &mut buffer);
+        assert_eq!(apu.square1.envelope.volume, 14);
+    }
+
+    #[test]
+    fn test_envelope_disabled_at_zero_pace() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[reg::NR12] = 0xF0; // initial volume 15, pace 0 (disabled)
+        io[reg::NR14] = 0x80; // trigger channel 1
+
+        let mut buffer = Vec::new();
+        apu.step(Envelope::PERIOD * 4, &io, &mut buffer);
+
+        assert_eq!(apu.square1.envelope.volume, 15);
+    }
+
+    #[test]
+    fn test_length_counter_silences_channel_when_enabled() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[reg::NR12] = 0xF0; // initial volume 15, envelope disabled
+        io[reg::NR11] = 0x3F; // length = 63, so the counter expires after 1 tick
+        io[reg::NR14] = 0xC0; // trigger + length enable
+
+        let mut buffer = Vec::new();
+        apu.step(1, &io, &mut buffer); // trigger fires, length loads 64-63=1
+        assert!(apu.square1.length_active());
+
+        // One 256 Hz length tick is 4_194_304 / 256 T-cycles.
+        apu.step(LengthCounter::PERIOD, &io, &mut buffer);
+        assert!(!apu.square1.length_active());
+    }
+
+    #[test]
+    fn test_length_counter_disabled_runs_forever() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[reg::NR11] = 0x3F; // length = 63
+        io[reg::NR14] = 0x80; // trigger only, length not enabled
+
+        let mut buffer = Vec::new();
+        apu.step(LengthCounter::PERIOD * 4, &io, &mut buffer);
+
+        assert!(apu.square1.length_active());
+    }
+
+    #[test]
+    fn test_sweep_shifts_frequency_up() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[reg::NR10] = 0x11; // pace 1, shift 1, increasing
+        io[reg::NR13] = 0x00; // freq lo
+        io[reg::NR14] = 0x84; // freq hi = 0x04 (freq=0x400), trigger
+
+        let mut buffer = Vec::new();
+        apu.step(1, &io, &mut buffer); // trigger fires, shadow freq = 0x400
+
+        // One pace-1 sweep tick is 4_194_304 / 128 T-cycles.
+        apu.step(Sweep::PERIOD, &io, &mut buffer);
+
+        // 0x400 + (0x400 >> 1) = 0x600
+        assert_eq!(apu.square1.sweep.shadow_freq, 0x600);
+    }
+
+    #[test]
+    fn test_sweep_overflow_silences_channel() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[reg::NR10] = 0x11; // pace 1, shift 1, increasing
+        io[reg::NR13] = 0xFF;
+        io[reg::NR14] = 0x87; // freq hi = 0x07 (freq=0x7FF, already near the 2047 ceiling), trigger
+
+        let mut buffer = Vec::new();
+        apu.step(1, &io, &mut buffer);
+        apu.step(Sweep::PERIOD, &io, &mut buffer);
+
+        assert!(apu.square1.sweep_silenced());
+    }
+
+    #[test]
+    fn test_sweep_disabled_when_pace_and_shift_are_zero() {
+        let mut apu = Apu::new();
+        let mut io = registers();
+        io[reg::NR10] = 0x00; // no sweep configured
+        io[reg::NR14] = 0x80; // trigger
+
+        let mut buffer = Vec::new();
+        apu.step(Sweep::PERIOD * 4, &io, &mut buffer);
+
+        assert!(!apu.square1.sweep_silenced());
+    }
+
+    #[test]
+    fn test_save_state_roundtrip() {
+        let mut apu = Apu::new();
+        let io = registers();
+        let mut buffer = Vec::new();
+        apu.step(CYCLES_PER_SAMPLE * 3 + 7, &io, &mut buffer);
+
+        let mut w = StateWriter::new();
+        apu.save_state(&mut w);
+
+        let mut restored = Apu::new();
+        let mut r = StateReader::new(&w.buf);
+        restored.load_state(&mut r).unwrap();
+
+        assert_eq!(restored.sample_counter, apu.sample_counter);
+        assert_eq!(restored.square1.phase, apu.square1.phase);
+    }
+}
@@ -1,34 +1,160 @@
 //! Memory Bus
 //!
 //! Handles all memory reads/writes with proper mapping:
-//! - 0x0000-0x7FFF: ROM
+//! - 0x0000-0x7FFF: ROM (via the cartridge's `Mapper`)
 //! - 0x8000-0x9FFF: VRAM
-//! - 0xA000-0xBFFF: External RAM
+//! - 0xA000-0xBFFF: External RAM (via the cartridge's `Mapper`)
 //! - 0xC000-0xDFFF: Work RAM
 //! - 0xFE00-0xFE9F: OAM
 //! - 0xFF00-0xFF7F: I/O Registers
 //! - 0xFF80-0xFFFE: High RAM
 
+use crate::cartridge::Cartridge;
+use crate::mapper::{self, Mapper};
+use crate::savestate::{StateReader, StateWriter};
+use crate::timer::Timer;
+
+/// Interrupt Flag (0xFF0F) / Interrupt Enable (0xFFFF) bit positions
+pub const INT_VBLANK: u8 = 0;
+pub const INT_STAT: u8 = 1;
+pub const INT_TIMER: u8 = 2;
+pub const INT_SERIAL: u8 = 3;
+pub const INT_JOYPAD: u8 = 4;
+
+/// No-op mapper used before a cartridge is loaded.
+struct NullMapper;
+
+impl Mapper for NullMapper {
+    fn read_rom(&self, _addr: u16) -> u8 {
+        0xFF
+    }
+
+    fn write_control(&mut self, _addr: u16, _value: u8) {}
+
+    fn read_ram(&self, _addr: u16) -> u8 {
+        0xFF
+    }
+
+    fn write_ram(&mut self, _addr: u16, _value: u8) {}
+
+    fn ram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn load_ram(&mut self, _data: &[u8]) {}
+
+    fn save_state(&self, _w: &mut StateWriter) {}
+
+    fn load_state(&mut self, _r: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// In-flight OAM DMA transfer (register 0xFF46)
+///
+/// Real hardware copies one byte every 4 T-cycles and locks the CPU out of
+/// OAM for the duration, so this is advanced alongside the CPU/PPU rather
+/// than performed as an instantaneous copy.
+#[derive(Default)]
+struct DmaState {
+    base: u8,
+    remaining: u8,
+}
+
+impl DmaState {
+    fn active(&self) -> bool {
+        self.remaining > 0
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.base);
+        w.u8(self.remaining);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.base = r.u8()?;
+        self.remaining = r.u8()?;
+        Ok(())
+    }
+}
+
+/// Joypad register (P1/0xFF00)
+///
+/// Real hardware multiplexes two 4-bit button groups onto the low nibble:
+/// bit 4 selects the direction pad, bit 5 selects the action buttons, and a
+/// selected, pressed line reads as 0.
+struct Joypad {
+    select: u8,    // last-written select bits (0x10/0x20), plus the unused high bits
+    direction: u8, // bits 0-3: Right, Left, Up, Down; 0 = pressed
+    action: u8,    // bits 0-3: A, B, Select, Start; 0 = pressed
+}
+
+impl Joypad {
+    fn new() -> Self {
+        Joypad {
+            select: 0xF0,
+            direction: 0x0F,
+            action: 0x0F,
+        }
+    }
+
+    fn write_select(&mut self, value: u8) {
+        self.select = value & 0xF0;
+    }
+
+    fn read(&self) -> u8 {
+        let mut low = 0x0F;
+        if self.select & 0x10 == 0 {
+            low &= self.direction;
+        }
+        if self.select & 0x20 == 0 {
+            low &= self.action;
+        }
+        self.select | low
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.select);
+        w.u8(self.direction);
+        w.u8(self.action);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.select = r.u8()?;
+        self.direction = r.u8()?;
+        self.action = r.u8()?;
+        Ok(())
+    }
+}
+
 pub struct Bus {
-    rom: Vec<u8>,
+    mapper: Box<dyn Mapper>,
     vram: [u8; 8192],
     wram: [u8; 8192],
     hram: [u8; 127],
     oam: [u8; 160],
     io: [u8; 128],
-    buttons: u8,
+    joypad: Joypad,
+    dma: DmaState,
+    timer: Timer,
+    boot_rom: Option<[u8; 256]>,
+    boot_rom_active: bool,
 }
 
 impl Bus {
     pub fn new() -> Self {
         Bus {
-            rom: vec![0; 32768],
+            mapper: Box::new(NullMapper),
             vram: [0; 8192],
             wram: [0; 8192],
             hram: [0; 127],
             oam: [0; 160],
             io: [0; 128],
-            buttons: 0xFF,  // All buttons released
+            joypad: Joypad::new(),
+            dma: DmaState::default(),
+            timer: Timer::new(),
+            boot_rom: None,
+            boot_rom_active: false,
         }
     }
 
@@ -38,26 +164,158 @@ impl Bus {
         self.hram.fill(0);
         self.oam.fill(0);
         self.io.fill(0);
-        self.buttons = 0xFF;
+        self.joypad = Joypad::new();
+        self.dma = DmaState::default();
+        self.timer.reset();
+        // A power-on remaps the boot ROM back over the cartridge if one is loaded.
+        self.boot_rom_active = self.boot_rom.is_some();
+    }
+
+    /// Map a 256-byte DMG boot ROM over 0x0000-0x00FF until the game writes
+    /// a non-zero value to 0xFF50.
+    pub fn load_boot_rom(&mut self, data: &[u8]) {
+        let mut rom = [0u8; 256];
+        let len = rom.len().min(data.len());
+        rom[..len].copy_from_slice(&data[..len]);
+
+        self.boot_rom = Some(rom);
+        self.boot_rom_active = true;
+    }
+
+    /// Whether a boot ROM has been supplied (regardless of whether it's
+    /// currently mapped in or has already been unmapped via 0xFF50).
+    pub fn has_boot_rom(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
+    /// Advance the timer by the given number of T-cycles, raising the timer
+    /// interrupt when TIMA overflows.
+    pub fn step_timer(&mut self, cycles: u32) {
+        if self.timer.step(cycles) {
+            self.request_interrupt(INT_TIMER);
+        }
+    }
+
+    /// Set the given bit in the IF (0xFF0F) register.
+    pub fn request_interrupt(&mut self, bit: u8) {
+        self.io[0x0F] |= 1 << bit;
+    }
+
+    /// The IE (0xFFFF) register.
+    pub fn interrupt_enable(&self) -> u8 {
+        self.io[0x7F]
+    }
+
+    /// The IF (0xFF0F) register.
+    pub fn interrupt_flag(&self) -> u8 {
+        self.io[0x0F]
+    }
+
+    /// Clear the given bit in the IF (0xFF0F) register, e.g. once its
+    /// interrupt has been dispatched.
+    pub fn clear_interrupt_flag(&mut self, bit: u8) {
+        self.io[0x0F] &= !(1 << bit);
+    }
+
+    /// Advance an in-flight OAM DMA transfer by the given number of T-cycles,
+    /// copying one byte every 4 cycles from `base << 8` into OAM.
+    pub fn step_dma(&mut self, cycles: u32) {
+        if !self.dma.active() {
+            return;
+        }
+
+        let mut ticks = cycles;
+        while ticks >= 4 && self.dma.active() {
+            let index = 0xA0 - self.dma.remaining;
+            let src = ((self.dma.base as u16) << 8) + index as u16;
+            let byte = self.read(src);
+            self.oam[index as usize] = byte;
+
+            self.dma.remaining -= 1;
+            ticks -= 4;
+        }
+    }
+
+    /// Start an OAM DMA transfer from source page `base << 8`.
+    fn init_request(&mut self, base: u8) {
+        self.dma.base = base;
+        self.dma.remaining = 0xA0;
+    }
+
+    /// Swap in the mapper for a freshly loaded cartridge.
+    pub fn load_cartridge(&mut self, cartridge: &Cartridge) {
+        self.mapper = mapper::make_mapper(cartridge);
+    }
+
+    /// Borrow the I/O registers, VRAM and OAM together, for the PPU to read
+    /// its LCD registers and render from while writing LY/STAT back.
+    pub fn ppu_memory(&mut self) -> (&mut [u8; 128], &[u8; 8192], &[u8; 160]) {
+        (&mut self.io, &self.vram, &self.oam)
+    }
+
+    /// Borrow the I/O registers, for the APU to read its sound registers from.
+    pub fn io(&self) -> &[u8; 128] {
+        &self.io
+    }
+
+    /// Export the cartridge RAM region for battery-backed save persistence.
+    pub fn export_sram(&self) -> Vec<u8> {
+        self.mapper.ram().to_vec()
+    }
+
+    /// Restore the cartridge RAM region from a previously exported save.
+    pub fn import_sram(&mut self, data: &[u8]) {
+        self.mapper.load_ram(data);
+    }
+
+    /// Serialize all RAM regions, I/O registers and peripheral state for a
+    /// save state. The mapper's ROM isn't included - the host re-supplies it
+    /// via `load_cartridge()` before restoring a save state.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.raw(&self.vram);
+        w.raw(&self.wram);
+        w.raw(&self.hram);
+        w.raw(&self.oam);
+        w.raw(&self.io);
+        self.joypad.save_state(w);
+        self.dma.save_state(w);
+        self.timer.save_state(w);
+        w.bool(self.boot_rom_active);
+        self.mapper.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        let len = self.vram.len();
+        self.vram.copy_from_slice(r.raw(len)?);
+        let len = self.wram.len();
+        self.wram.copy_from_slice(r.raw(len)?);
+        let len = self.hram.len();
+        self.hram.copy_from_slice(r.raw(len)?);
+        let len = self.oam.len();
+        self.oam.copy_from_slice(r.raw(len)?);
+        let len = self.io.len();
+        self.io.copy_from_slice(r.raw(len)?);
+        self.joypad.load_state(r)?;
+        self.dma.load_state(r)?;
+        self.timer.load_state(r)?;
+        self.boot_rom_active = r.bool()?;
+        self.mapper.load_state(r)?;
+        Ok(())
     }
 
     pub fn read(&self, addr: u16) -> u8 {
         match addr {
-            // ROM
-            0x0000..=0x7FFF => {
-                let offset = addr as usize;
-                if offset < self.rom.len() {
-                    self.rom[offset]
-                } else {
-                    0xFF
-                }
+            // ROM (the boot ROM overlays the first 256 bytes while active)
+            0x0000..=0x00FF if self.boot_rom_active => {
+                self.boot_rom.as_ref().map(|rom| rom[addr as usize]).unwrap_or(0xFF)
             }
+            0x0000..=0x7FFF => self.mapper.read_rom(addr),
 
             // VRAM
             0x8000..=0x9FFF => self.vram[(addr - 0x8000) as usize],
 
-            // External RAM (not implemented yet)
-            0xA000..=0xBFFF => 0xFF,
+            // External RAM (dispatched to the cartridge's mapper)
+            0xA000..=0xBFFF => self.mapper.read_ram(addr - 0xA000),
 
             // Work RAM
             0xC000..=0xDFFF => self.wram[(addr - 0xC000) as usize],
@@ -65,21 +323,30 @@ impl Bus {
             // Echo RAM (mirrors WRAM)
             0xE000..=0xFDFF => self.wram[(addr - 0xE000) as usize],
 
-            // OAM
-            0xFE00..=0xFE9F => self.oam[(addr - 0xFE00) as usize],
+            // OAM (bus conflict: reads return garbage while DMA owns it)
+            0xFE00..=0xFE9F => {
+                if self.dma.active() {
+                    0xFF
+                } else {
+                    self.oam[(addr - 0xFE00) as usize]
+                }
+            }
 
             // Unusable
             0xFEA0..=0xFEFF => 0xFF,
 
-            // I/O Registers
-            0xFF00..=0xFF7F => {
-                if addr == 0xFF00 {
-                    // Joypad register
-                    self.buttons
-                } else {
-                    self.io[(addr - 0xFF00) as usize]
-                }
-            }
+            // Joypad register
+            0xFF00 => self.joypad.read(),
+
+            // Timer registers
+            0xFF04 => self.timer.div(),
+            0xFF05 => self.timer.tima(),
+            0xFF06 => self.timer.tma(),
+            0xFF07 => self.timer.tac(),
+
+            // I/O Registers (everything else in the block, not backed by its
+            // own component above)
+            0xFF01..=0xFF03 | 0xFF08..=0xFF7F => self.io[(addr - 0xFF00) as usize],
 
             // High RAM
             0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize],
@@ -93,18 +360,14 @@ impl Bus {
 
     pub fn write(&mut self, addr: u16, value: u8) {
         match addr {
-            // ROM (read-only, but MBC commands go here)
-            0x0000..=0x7FFF => {
-                // TODO: Handle MBC commands
-            }
+            // ROM (read-only, but writes here are MBC bank-switch commands)
+            0x0000..=0x7FFF => self.mapper.write_control(addr, value),
 
             // VRAM
             0x8000..=0x9FFF => self.vram[(addr - 0x8000) as usize] = value,
 
             // External RAM
-            0xA000..=0xBFFF => {
-                // TODO: Handle cartridge RAM
-            }
+            0xA000..=0xBFFF => self.mapper.write_ram(addr - 0xA000, value),
 
             // Work RAM
             0xC000..=0xDFFF => self.wram[(addr - 0xC000) as usize] = value,
@@ -118,8 +381,34 @@ impl Bus {
             // Unusable
             0xFEA0..=0xFEFF => {}
 
-            // I/O Registers
-            0xFF00..=0xFF7F => self.io[(addr - 0xFF00) as usize] = value,
+            // Joypad register
+            0xFF00 => self.joypad.write_select(value),
+
+            // Boot ROM disable: any non-zero write permanently unmaps it
+            0xFF50 => {
+                self.io[(addr - 0xFF00) as usize] = value;
+                if value != 0 {
+                    self.boot_rom_active = false;
+                }
+            }
+
+            // OAM DMA transfer request
+            0xFF46 => {
+                self.io[(addr - 0xFF00) as usize] = value;
+                self.init_request(value);
+            }
+
+            // Timer registers
+            0xFF04 => self.timer.reset_div(),
+            0xFF05 => self.timer.set_tima(value),
+            0xFF06 => self.timer.set_tma(value),
+            0xFF07 => self.timer.set_tac(value),
+
+            // I/O Registers (everything else in the block, not backed by its
+            // own component above)
+            0xFF01..=0xFF03 | 0xFF08..=0xFF45 | 0xFF47..=0xFF4F | 0xFF51..=0xFF7F => {
+                self.io[(addr - 0xFF00) as usize] = value
+            }
 
             // High RAM
             0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize] = value,
@@ -131,18 +420,43 @@ impl Bus {
         }
     }
 
+    /// Set button state
+    ///
+    /// `button` is 0=A, 1=B, 2=Start, 3=Select, 4=Up, 5=Down, 6=Left, 7=Right.
+    /// Raises the Joypad interrupt on a high-to-low transition of a line
+    /// that's currently selected via the P1 register.
     pub fn set_button(&mut self, button: u8, pressed: bool) {
-        if button < 8 {
-            if pressed {
-                self.buttons &= !(1 << button);
-            } else {
-                self.buttons |= 1 << button;
-            }
+        let (bit, is_direction) = match button {
+            0 => (0, false), // A
+            1 => (1, false), // B
+            2 => (3, false), // Start
+            3 => (2, false), // Select
+            4 => (2, true),  // Up
+            5 => (3, true),  // Down
+            6 => (1, true),  // Left
+            7 => (0, true),  // Right
+            _ => return,
+        };
+
+        let nibble = if is_direction { self.joypad.direction } else { self.joypad.action };
+        let was_pressed = nibble & (1 << bit) == 0;
+        let updated = if pressed { nibble & !(1 << bit) } else { nibble | (1 << bit) };
+
+        if is_direction {
+            self.joypad.direction = updated;
+        } else {
+            self.joypad.action = updated;
         }
-    }
 
-    pub fn load_rom(&mut self, data: &[u8]) {
-        self.rom = data.to_vec();
+        let selected = if is_direction {
+            self.joypad.select & 0x10 == 0
+        } else {
+            self.joypad.select & 0x20 == 0
+        };
+
+        if pressed && !was_pressed && selected {
+            self.request_interrupt(INT_JOYPAD);
+        }
     }
 }
 
@@ -165,10 +479,137 @@ mod tests {
     fn test_button_input() {
         let mut bus = Bus::new();
 
-        bus.set_button(0, true);  // Press A
-        assert_eq!(bus.buttons & 0x01, 0x00);
+        // Select the action group so A is visible on the low nibble
+        bus.write(0xFF00, 0x10);
+
+        bus.set_button(0, true); // Press A
+        assert_eq!(bus.read(0xFF00) & 0x01, 0x00);
+
+        bus.set_button(0, false); // Release A
+        assert_eq!(bus.read(0xFF00) & 0x01, 0x01);
+    }
+
+    #[test]
+    fn test_joypad_select_lines_are_independent() {
+        let mut bus = Bus::new();
+
+        bus.set_button(0, true); // Press A (action)
+        bus.set_button(4, true); // Press Up (direction)
+
+        bus.write(0xFF00, 0x10); // select action group
+        assert_eq!(bus.read(0xFF00) & 0x0F, 0x0E); // A pressed, B/Select/Start released
+
+        bus.write(0xFF00, 0x20); // select direction group
+        assert_eq!(bus.read(0xFF00) & 0x0F, 0x0B); // Up pressed, others released
+    }
+
+    #[test]
+    fn test_joypad_interrupt_on_selected_press() {
+        let mut bus = Bus::new();
+
+        bus.write(0xFF00, 0x10); // select action group
+        bus.set_button(0, true); // Press A while selected
+
+        assert_ne!(bus.interrupt_flag() & 0x10, 0);
+    }
+
+    #[test]
+    fn test_joypad_no_interrupt_when_deselected() {
+        let mut bus = Bus::new();
+
+        bus.write(0xFF00, 0x20); // select direction group, action deselected
+        bus.set_button(0, true); // Press A while its line is deselected
+
+        assert_eq!(bus.interrupt_flag() & 0x10, 0);
+    }
+
+    #[test]
+    fn test_rom_routed_through_mapper() {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x147] = 0x00; // ROM only
+        rom[0x148] = 0x00;
+        rom[0x0100] = 0x42;
+
+        let cart = Cartridge::from_rom(&rom).unwrap();
+        let mut bus = Bus::new();
+        bus.load_cartridge(&cart);
+
+        assert_eq!(bus.read(0x0100), 0x42);
+    }
+
+    #[test]
+    fn test_oam_dma_transfer() {
+        let mut bus = Bus::new();
+
+        // Source page 0xC1 (work RAM), filled with a recognisable pattern
+        for i in 0..0xA0u16 {
+            bus.write(0xC100 + i, i as u8);
+        }
+
+        bus.write(0xFF46, 0xC1);
+
+        // Reads during the transfer are garbage (bus conflict)
+        assert_eq!(bus.read(0xFE00), 0xFF);
+
+        // 160 bytes at 4 cycles each
+        bus.step_dma(0xA0 * 4);
+
+        assert!(!bus.dma.active());
+        for i in 0..0xA0u16 {
+            assert_eq!(bus.oam[i as usize], i as u8);
+        }
+    }
+
+    #[test]
+    fn test_boot_rom_overlay_and_unmap() {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x147] = 0x00;
+        rom[0x148] = 0x00;
+        rom[0x0000] = 0x42; // cartridge byte, shadowed by the boot ROM
+
+        let cart = Cartridge::from_rom(&rom).unwrap();
+        let mut bus = Bus::new();
+        bus.load_cartridge(&cart);
+
+        let mut boot = [0u8; 256];
+        boot[0] = 0x31;
+        bus.load_boot_rom(&boot);
+
+        assert!(bus.has_boot_rom());
+        assert_eq!(bus.read(0x0000), 0x31);
+
+        // A zero write to 0xFF50 leaves the boot ROM mapped in
+        bus.write(0xFF50, 0x00);
+        assert_eq!(bus.read(0x0000), 0x31);
+
+        // A non-zero write permanently unmaps it, exposing the cartridge again
+        bus.write(0xFF50, 0x01);
+        assert_eq!(bus.read(0x0000), 0x42);
+    }
+
+    #[test]
+    fn test_save_state_roundtrip() {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x147] = 0x00;
+        rom[0x148] = 0x00;
+
+        let cart = Cartridge::from_rom(&rom).unwrap();
+        let mut bus = Bus::new();
+        bus.load_cartridge(&cart);
+
+        bus.write(0xC000, 0x42);
+        bus.write(0xFF00, 0x10);
+        bus.set_button(0, true);
+
+        let mut w = StateWriter::new();
+        bus.save_state(&mut w);
+
+        let mut restored = Bus::new();
+        restored.load_cartridge(&cart);
+        let mut r = StateReader::new(&w.buf);
+        restored.load_state(&mut r).unwrap();
 
-        bus.set_button(0, false);  // Release A
-        assert_eq!(bus.buttons & 0x01, 0x01);
+        assert_eq!(restored.read(0xC000), 0x42);
+        assert_eq!(restored.read(0xFF00) & 0x0F, bus.read(0xFF00) & 0x0F);
     }
 }
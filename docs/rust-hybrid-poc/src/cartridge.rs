@@ -16,11 +16,24 @@ pub enum CartridgeType {
 pub struct CartridgeHeader {
     pub title: String,
     pub cartridge_type: CartridgeType,
+    pub cartridge_type_byte: u8,
     pub rom_size: usize,
     pub ram_size: usize,
     pub cgb_flag: u8,
 }
 
+/// Cartridge type bytes (0x147) that wire a battery to the external RAM,
+/// letting it survive power-off.
+const BATTERY_BACKED_TYPES: &[u8] = &[0x03, 0x06, 0x09, 0x0D, 0x0F, 0x10, 0x13, 0x1B, 0x1E, 0x22, 0xFF];
+
+impl CartridgeHeader {
+    /// Whether this cartridge's external RAM is battery-backed and should
+    /// be persisted across sessions.
+    pub fn is_battery_backed(&self) -> bool {
+        BATTERY_BACKED_TYPES.contains(&self.cartridge_type_byte)
+    }
+}
+
 /// Cartridge (ROM + optional RAM)
 pub struct Cartridge {
     pub header: CartridgeHeader,
@@ -84,6 +97,7 @@ impl Cartridge {
         Ok(CartridgeHeader {
             title,
             cartridge_type,
+            cartridge_type_byte: cart_type_byte,
             rom_size,
             ram_size,
             cgb_flag,
@@ -117,4 +131,17 @@ mod tests {
         assert_eq!(cart.header.rom_size, 32768);
         assert_eq!(cart.header.ram_size, 0);
     }
+
+    #[test]
+    fn test_is_battery_backed() {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x148] = 0x00;
+        rom[0x149] = 0x02;
+
+        rom[0x147] = 0x03; // MBC1+RAM+BATTERY
+        assert!(Cartridge::from_rom(&rom).unwrap().header.is_battery_backed());
+
+        rom[0x147] = 0x01; // MBC1 (no battery)
+        assert!(!Cartridge::from_rom(&rom).unwrap().header.is_battery_backed());
+    }
 }
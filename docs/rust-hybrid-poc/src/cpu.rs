@@ -3,6 +3,7 @@
 //! 8-bit CPU with 16-bit address space, similar to Z80 but with some differences.
 
 use crate::bus::Bus;
+use crate::savestate::{StateReader, StateWriter};
 
 /// CPU registers
 pub struct Registers {
@@ -32,6 +33,7 @@ pub struct Cpu {
 }
 
 impl Cpu {
+    /// Post-boot register state, as if the DMG boot ROM had already run.
     pub fn new() -> Self {
         Cpu {
             regs: Registers {
@@ -51,12 +53,49 @@ impl Cpu {
         }
     }
 
-    pub fn reset(&mut self) {
-        *self = Self::new();
+    /// Zeroed register state with PC at 0x0000, for running the actual boot ROM.
+    fn new_for_boot_rom() -> Self {
+        Cpu {
+            regs: Registers {
+                a: 0,
+                f: 0,
+                b: 0,
+                c: 0,
+                d: 0,
+                e: 0,
+                h: 0,
+                l: 0,
+                sp: 0,
+                pc: 0x0000,
+            },
+            ime: false,
+            halted: false,
+        }
+    }
+
+    /// Reset to either the post-boot defaults, or zeroed boot-ROM entry state
+    /// when a boot ROM is mapped in and about to run.
+    pub fn reset(&mut self, boot_rom_loaded: bool) {
+        *self = if boot_rom_loaded {
+            Self::new_for_boot_rom()
+        } else {
+            Self::new()
+        };
     }
 
     /// Execute one instruction and return cycles consumed
     pub fn step(&mut self, bus: &mut Bus) -> u32 {
+        let pending = bus.interrupt_enable() & bus.interrupt_flag() & 0x1F;
+
+        // Any pending, enabled interrupt wakes the CPU even with IME clear.
+        if pending != 0 {
+            self.halted = false;
+        }
+
+        if self.ime && pending != 0 {
+            return self.service_interrupt(bus, pending);
+        }
+
         if self.halted {
             return 4;
         }
@@ -69,6 +108,68 @@ impl Cpu {
         self.execute(opcode, bus)
     }
 
+    /// Dispatch the highest-priority pending interrupt: push PC, clear IME
+    /// and the serviced IF bit, and jump to the interrupt's vector.
+    fn service_interrupt(&mut self, bus: &mut Bus, pending: u8) -> u32 {
+        self.ime = false;
+
+        let bit = pending.trailing_zeros() as u8;
+        bus.clear_interrupt_flag(bit);
+
+        self.push(bus, self.regs.pc);
+        self.regs.pc = match bit {
+            0 => 0x40, // VBlank
+            1 => 0x48, // STAT
+            2 => 0x50, // Timer
+            3 => 0x58, // Serial
+            4 => 0x60, // Joypad
+            _ => unreachable!(),
+        };
+
+        20
+    }
+
+    /// Serialize registers and interrupt state for save states.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.regs.a);
+        w.u8(self.regs.f);
+        w.u8(self.regs.b);
+        w.u8(self.regs.c);
+        w.u8(self.regs.d);
+        w.u8(self.regs.e);
+        w.u8(self.regs.h);
+        w.u8(self.regs.l);
+        w.u16(self.regs.sp);
+        w.u16(self.regs.pc);
+        w.bool(self.ime);
+        w.bool(self.halted);
+    }
+
+    /// Restore registers and interrupt state from a save state.
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.regs.a = r.u8()?;
+        self.regs.f = r.u8()?;
+        self.regs.b = r.u8()?;
+        self.regs.c = r.u8()?;
+        self.regs.d = r.u8()?;
+        self.regs.e = r.u8()?;
+        self.regs.h = r.u8()?;
+        self.regs.l = r.u8()?;
+        self.regs.sp = r.u16()?;
+        self.regs.pc = r.u16()?;
+        self.ime = r.bool()?;
+        self.halted = r.bool()?;
+        Ok(())
+    }
+
+    /// Push a 16-bit value onto the stack, high byte first.
+    fn push(&mut self, bus: &mut Bus, value: u16) {
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+        bus.write(self.regs.sp, (value >> 8) as u8);
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+        bus.write(self.regs.sp, value as u8);
+    }
+
     /// Execute a single instruction
     fn execute(&mut self, opcode: u8, bus: &mut Bus) -> u32 {
         match opcode {
@@ -197,4 +298,63 @@ mod tests {
         assert_eq!(result, 0x00);
         assert_ne!(cpu.regs.f & FLAG_Z, 0);
     }
+
+    #[test]
+    fn test_vblank_interrupt_dispatch() {
+        let mut cpu = Cpu::new();
+        let mut bus = Bus::new();
+
+        cpu.ime = true;
+        cpu.regs.pc = 0x1234;
+        bus.write(0xFFFF, 0x01); // IE: VBlank enabled
+        bus.request_interrupt(0); // IF: VBlank pending
+
+        let cycles = cpu.step(&mut bus);
+
+        assert_eq!(cycles, 20);
+        assert_eq!(cpu.regs.pc, 0x40);
+        assert!(!cpu.ime);
+        assert_eq!(bus.interrupt_flag() & 0x01, 0);
+
+        // Pushed return address is readable back off the stack
+        let low = bus.read(cpu.regs.sp);
+        let high = bus.read(cpu.regs.sp.wrapping_add(1));
+        assert_eq!(u16::from_le_bytes([low, high]), 0x1234);
+    }
+
+    #[test]
+    fn test_pending_interrupt_wakes_halted_cpu_without_ime() {
+        let mut cpu = Cpu::new();
+        let mut bus = Bus::new();
+
+        cpu.halted = true;
+        cpu.ime = false;
+        bus.write(0xFFFF, 0x01);
+        bus.request_interrupt(0);
+
+        cpu.step(&mut bus);
+
+        assert!(!cpu.halted);
+    }
+
+    #[test]
+    fn test_save_state_roundtrip() {
+        let mut cpu = Cpu::new();
+        cpu.regs.a = 0x12;
+        cpu.regs.pc = 0xABCD;
+        cpu.ime = true;
+        cpu.halted = true;
+
+        let mut w = StateWriter::new();
+        cpu.save_state(&mut w);
+
+        let mut restored = Cpu::new();
+        let mut r = StateReader::new(&w.buf);
+        restored.load_state(&mut r).unwrap();
+
+        assert_eq!(restored.regs.a, 0x12);
+        assert_eq!(restored.regs.pc, 0xABCD);
+        assert!(restored.ime);
+        assert!(restored.halted);
+    }
 }
@@ -6,15 +6,28 @@
 use wasm_bindgen::prelude::*;
 use js_sys::{Uint8Array, Uint8ClampedArray, Float32Array};
 
+mod apu;
 mod cpu;
 mod ppu;
 mod bus;
 mod cartridge;
+mod mapper;
+mod savestate;
+mod timer;
 
+use apu::Apu;
 use cpu::Cpu;
-use ppu::Ppu;
+use ppu::{Ppu, INT_STAT, INT_VBLANK};
 use bus::Bus;
 use cartridge::Cartridge;
+use savestate::{StateReader, StateWriter};
+
+/// Save state format identifier, to reject blobs from an unrelated source.
+const SAVE_STATE_MAGIC: &[u8; 4] = b"PHBY";
+
+/// Save state format version, bumped whenever the layout written by
+/// `get_state()` changes so old states are rejected instead of misread.
+const SAVE_STATE_VERSION: u8 = 1;
 
 /// Screen dimensions
 const SCREEN_WIDTH: usize = 160;
@@ -32,6 +45,7 @@ const CYCLES_PER_FRAME: u32 = 70224;
 pub struct GameBoyCore {
     cpu: Cpu,
     ppu: Ppu,
+    apu: Apu,
     bus: Bus,
     cartridge: Option<Cartridge>,
     framebuffer: Box<[u8; SCREEN_PIXELS]>,
@@ -51,10 +65,12 @@ impl GameBoyCore {
         let bus = Bus::new();
         let cpu = Cpu::new();
         let ppu = Ppu::new();
+        let apu = Apu::new();
 
         Ok(GameBoyCore {
             cpu,
             ppu,
+            apu,
             bus,
             cartridge: None,
             framebuffer: Box::new([0u8; SCREEN_PIXELS]),
@@ -75,6 +91,7 @@ impl GameBoyCore {
         let cartridge = Cartridge::from_rom(rom_data)
             .map_err(|e| JsValue::from_str(&format!("Failed to load ROM: {}", e)))?;
 
+        self.bus.load_cartridge(&cartridge);
         self.cartridge = Some(cartridge);
         self.reset();
 
@@ -88,15 +105,28 @@ impl GameBoyCore {
     #[wasm_bindgen]
     pub fn step(&mut self) {
         let mut cycles_this_frame = 0;
+        self.audio_buffer.clear();
 
         while cycles_this_frame < CYCLES_PER_FRAME {
             // Execute one CPU instruction
             let cycles = self.cpu.step(&mut self.bus);
 
-            // Update PPU (generates pixels)
-            self.ppu.step(cycles, &mut self.framebuffer);
+            // Update PPU (generates pixels) and raise any LCD interrupts
+            let (io, vram, oam) = self.bus.ppu_memory();
+            let lcd_interrupts = self.ppu.step(cycles, io, vram, oam, &mut self.framebuffer);
+            if lcd_interrupts & INT_VBLANK != 0 {
+                self.bus.request_interrupt(bus::INT_VBLANK);
+            }
+            if lcd_interrupts & INT_STAT != 0 {
+                self.bus.request_interrupt(bus::INT_STAT);
+            }
+
+            // Advance any in-flight OAM DMA transfer and the timer
+            self.bus.step_dma(cycles);
+            self.bus.step_timer(cycles);
 
-            // TODO: Update APU (generates audio)
+            // Update APU (generates audio)
+            self.apu.step(cycles, self.bus.io(), &mut self.audio_buffer);
 
             cycles_this_frame += cycles;
             self.cycle_count += cycles;
@@ -138,12 +168,24 @@ impl GameBoyCore {
         self.bus.set_button(button, pressed);
     }
 
+    /// Load a DMG boot ROM, to be mapped over 0x0000-0x00FF until the game
+    /// unmaps it with a write to 0xFF50
+    ///
+    /// # Arguments
+    /// * `boot_rom_data` - The 256-byte boot ROM image
+    #[wasm_bindgen]
+    pub fn load_boot_rom(&mut self, boot_rom_data: &[u8]) {
+        self.bus.load_boot_rom(boot_rom_data);
+        self.reset();
+    }
+
     /// Reset the emulator to initial state
     #[wasm_bindgen]
     pub fn reset(&mut self) {
-        self.cpu.reset();
-        self.ppu.reset();
         self.bus.reset();
+        self.cpu.reset(self.bus.has_boot_rom());
+        self.ppu.reset();
+        self.apu.reset();
         self.cycle_count = 0;
         self.framebuffer.fill(255); // White screen
         self.audio_buffer.clear();
@@ -151,22 +193,58 @@ impl GameBoyCore {
 
     /// Get serialized state for save states
     ///
-    /// Returns a byte array containing all emulator state.
-    /// Can be stored in localStorage and restored later.
+    /// Returns a byte array containing all emulator state: CPU registers,
+    /// the full memory map, the mapper's banking registers and cartridge
+    /// RAM, and the PPU/APU's in-progress rendering/audio state. Can be
+    /// stored in localStorage and restored later with `set_state()`.
     #[wasm_bindgen]
     pub fn get_state(&self) -> Vec<u8> {
-        // TODO: Implement proper serialization
-        // For now, return empty vec
-        Vec::new()
+        let mut w = StateWriter::new();
+        w.raw(SAVE_STATE_MAGIC);
+        w.u8(SAVE_STATE_VERSION);
+
+        self.cpu.save_state(&mut w);
+        self.bus.save_state(&mut w);
+        self.ppu.save_state(&mut w);
+        self.apu.save_state(&mut w);
+        w.u32(self.cycle_count);
+
+        w.buf
     }
 
     /// Restore from serialized state
     ///
     /// # Arguments
-    /// * `state` - Byte array from previous get_state() call
+    /// * `state` - Byte array from a previous `get_state()` call, for a ROM
+    ///   already loaded via `load_rom()`
+    ///
+    /// # Errors
+    /// Returns an error if `state` isn't a recognized save state, or was
+    /// written by an incompatible version of this core.
     #[wasm_bindgen]
-    pub fn set_state(&mut self, _state: &[u8]) -> Result<(), JsValue> {
-        // TODO: Implement deserialization
+    pub fn set_state(&mut self, state: &[u8]) -> Result<(), JsValue> {
+        let mut r = StateReader::new(state);
+        let to_js_err = |e: String| JsValue::from_str(&format!("Failed to load save state: {}", e));
+
+        let magic = r.raw(SAVE_STATE_MAGIC.len()).map_err(to_js_err)?;
+        if magic != SAVE_STATE_MAGIC {
+            return Err(to_js_err("not a PHPBoy save state".to_string()));
+        }
+
+        let version = r.u8().map_err(to_js_err)?;
+        if version != SAVE_STATE_VERSION {
+            return Err(to_js_err(format!(
+                "unsupported save state version {} (expected {})",
+                version, SAVE_STATE_VERSION
+            )));
+        }
+
+        self.cpu.load_state(&mut r).map_err(to_js_err)?;
+        self.bus.load_state(&mut r).map_err(to_js_err)?;
+        self.ppu.load_state(&mut r).map_err(to_js_err)?;
+        self.apu.load_state(&mut r).map_err(to_js_err)?;
+        self.cycle_count = r.u32().map_err(to_js_err)?;
+
         Ok(())
     }
 
@@ -176,6 +254,31 @@ impl GameBoyCore {
         self.cycle_count
     }
 
+    /// Export the cartridge's external RAM, for battery-backed save persistence
+    ///
+    /// Only the RAM region is serialized, so this is cheap enough to call on
+    /// every VBlank or page unload; pair it with `is_battery_backed()` to
+    /// decide when persisting is actually worthwhile.
+    #[wasm_bindgen]
+    pub fn export_sram(&self) -> Vec<u8> {
+        self.bus.export_sram()
+    }
+
+    /// Restore the cartridge's external RAM from a previous `export_sram()` call
+    #[wasm_bindgen]
+    pub fn import_sram(&mut self, data: &[u8]) {
+        self.bus.import_sram(data);
+    }
+
+    /// Whether the loaded cartridge has battery-backed RAM that should be persisted
+    #[wasm_bindgen]
+    pub fn is_battery_backed(&self) -> bool {
+        self.cartridge
+            .as_ref()
+            .map(|c| c.header.is_battery_backed())
+            .unwrap_or(false)
+    }
+
     /// Get memory pointer (for advanced zero-copy access)
     ///
     /// Returns the base address of the framebuffer in WASM linear memory.
@@ -0,0 +1,255 @@
+//! Memory Bank Controllers (MBC)
+//!
+//! Owns the cartridge ROM/RAM and translates CPU-visible addresses into
+//! the correct bank, reacting to the "control writes" games make into the
+//! 0x0000-0x7FFF ROM range to switch banks.
+
+use crate::cartridge::{Cartridge, CartridgeType};
+use crate::savestate::{StateReader, StateWriter};
+
+/// Bank-switching behaviour for a cartridge's ROM/RAM.
+pub trait Mapper {
+    fn read_rom(&self, addr: u16) -> u8;
+    fn write_control(&mut self, addr: u16, value: u8);
+    fn read_ram(&self, addr: u16) -> u8;
+    fn write_ram(&mut self, addr: u16, value: u8);
+
+    /// The full cartridge RAM region, for battery-backed save persistence.
+    fn ram(&self) -> &[u8];
+
+    /// Restore the full cartridge RAM region from a previously exported save.
+    fn load_ram(&mut self, data: &[u8]);
+
+    /// Serialize banking registers and cartridge RAM for a save state. ROM
+    /// itself isn't included - it's immutable and the host re-supplies it
+    /// via `load_rom()` before restoring a save state.
+    fn save_state(&self, w: &mut StateWriter);
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String>;
+}
+
+/// No bank switching; ROM is read directly, RAM is whatever the cartridge shipped with.
+struct RomOnlyMapper {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+}
+
+impl Mapper for RomOnlyMapper {
+    fn read_rom(&self, addr: u16) -> u8 {
+        self.rom.get(addr as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_control(&mut self, _addr: u16, _value: u8) {}
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        self.ram.get(addr as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if let Some(byte) = self.ram.get_mut(addr as usize) {
+            *byte = value;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        let ram = r.bytes()?;
+        self.load_ram(ram);
+        Ok(())
+    }
+}
+
+/// MBC1: up to 2 MB ROM / 32 KB RAM, with the classic ROM/RAM banking-mode quirk.
+struct Mbc1Mapper {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank_low: u8,
+    bank_high: u8,
+    ram_banking_mode: bool,
+}
+
+impl Mbc1Mapper {
+    fn new(rom: Vec<u8>, ram: Vec<u8>) -> Self {
+        Mbc1Mapper {
+            rom,
+            ram,
+            ram_enabled: false,
+            rom_bank_low: 1,
+            bank_high: 0,
+            ram_banking_mode: false,
+        }
+    }
+
+    /// The bank mapped into the switchable 0x4000-0x7FFF window.
+    fn rom_bank(&self) -> usize {
+        let low = if self.rom_bank_low == 0 { 1 } else { self.rom_bank_low };
+        let bank = if self.ram_banking_mode {
+            low as usize
+        } else {
+            ((self.bank_high << 5) | low) as usize
+        };
+        bank
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.ram_banking_mode {
+            self.bank_high as usize
+        } else {
+            0
+        }
+    }
+}
+
+impl Mapper for Mbc1Mapper {
+    fn read_rom(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom.get(addr as usize).copied().unwrap_or(0xFF),
+            0x4000..=0x7FFF => {
+                let offset = self.rom_bank() * 0x4000 + (addr - 0x4000) as usize;
+                self.rom.get(offset).copied().unwrap_or(0xFF)
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_control(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank_low = value & 0x1F,
+            0x4000..=0x5FFF => self.bank_high = value & 0x03,
+            0x6000..=0x7FFF => self.ram_banking_mode = (value & 0x01) != 0,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        let offset = self.ram_bank() * 0x2000 + addr as usize;
+        self.ram.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        let offset = self.ram_bank() * 0x2000 + addr as usize;
+        if let Some(byte) = self.ram.get_mut(offset) {
+            *byte = value;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.ram_enabled);
+        w.u8(self.rom_bank_low);
+        w.u8(self.bank_high);
+        w.bool(self.ram_banking_mode);
+        w.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.ram_enabled = r.bool()?;
+        self.rom_bank_low = r.u8()?;
+        self.bank_high = r.u8()?;
+        self.ram_banking_mode = r.bool()?;
+        let ram = r.bytes()?;
+        self.load_ram(ram);
+        Ok(())
+    }
+}
+
+/// Build the mapper appropriate for a parsed cartridge.
+///
+/// MBC3/MBC5 are not banked; they use the ROM-only mapper, so ROMs of up to
+/// 32 KB work and anything beyond the first 32 KB of ROM is unreachable.
+pub fn make_mapper(cartridge: &Cartridge) -> Box<dyn Mapper> {
+    match cartridge.header.cartridge_type {
+        CartridgeType::Mbc1 => Box::new(Mbc1Mapper::new(
+            cartridge.rom.clone(),
+            cartridge.ram.clone(),
+        )),
+        _ => Box::new(RomOnlyMapper {
+            rom: cartridge.rom.clone(),
+            ram: cartridge.ram.clone(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mbc1_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0u8; banks * 0x4000];
+        for bank in 0..banks {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        rom
+    }
+
+    #[test]
+    fn test_mbc1_bank_zero_forced_to_one() {
+        let mut mapper = Mbc1Mapper::new(mbc1_rom(4), vec![0; 0x2000]);
+        mapper.write_control(0x2000, 0x00);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+    }
+
+    #[test]
+    fn test_mbc1_switches_rom_bank() {
+        let mut mapper = Mbc1Mapper::new(mbc1_rom(4), vec![0; 0x2000]);
+        mapper.write_control(0x2000, 0x03);
+        assert_eq!(mapper.read_rom(0x4000), 3);
+    }
+
+    #[test]
+    fn test_mbc1_ram_requires_enable() {
+        let mut mapper = Mbc1Mapper::new(mbc1_rom(2), vec![0; 0x2000]);
+        mapper.write_ram(0x0000, 0x42);
+        assert_eq!(mapper.read_ram(0x0000), 0xFF);
+
+        mapper.write_control(0x0000, 0x0A);
+        mapper.write_ram(0x0000, 0x42);
+        assert_eq!(mapper.read_ram(0x0000), 0x42);
+    }
+
+    #[test]
+    fn test_mbc1_save_state_roundtrip() {
+        let mut mapper = Mbc1Mapper::new(mbc1_rom(4), vec![0; 0x2000]);
+        mapper.write_control(0x0000, 0x0A); // enable RAM
+        mapper.write_control(0x2000, 0x03); // ROM bank 3
+        mapper.write_ram(0x0000, 0x99);
+
+        let mut w = StateWriter::new();
+        mapper.save_state(&mut w);
+
+        let mut restored = Mbc1Mapper::new(mbc1_rom(4), vec![0; 0x2000]);
+        let mut r = StateReader::new(&w.buf);
+        restored.load_state(&mut r).unwrap();
+
+        assert_eq!(restored.read_rom(0x4000), 3);
+        assert_eq!(restored.read_ram(0x0000), 0x99);
+    }
+}
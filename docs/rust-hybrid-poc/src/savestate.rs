@@ -0,0 +1,128 @@
+//! Save-state binary format
+//!
+//! A flat, versioned byte cursor used by `GameBoyCore::get_state()`/`set_state()`
+//! and the component `save_state`/`load_state` methods it delegates to. Every
+//! value is little-endian and fields are written/read in a fixed order, so
+//! there's no need for per-field tags.
+
+/// Appends values to a growing byte buffer.
+pub struct StateWriter {
+    pub buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter { buf: Vec::new() }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Writes a length-prefixed byte slice, for fields whose size varies by cartridge.
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.buf.extend_from_slice(value);
+    }
+
+    /// Writes a fixed-size byte slice with no length prefix, for a field whose
+    /// size is already known to the reader (e.g. a `[u8; N]` array).
+    pub fn raw(&mut self, value: &[u8]) {
+        self.buf.extend_from_slice(value);
+    }
+}
+
+/// Reads values back off a byte slice in the same order `StateWriter` wrote them.
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        StateReader { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).ok_or("save state is truncated")?;
+        let slice = self.data.get(self.pos..end).ok_or("save state is truncated")?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    pub fn raw(&mut self, len: usize) -> Result<&'a [u8], String> {
+        self.take(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut w = StateWriter::new();
+        w.u8(0x42);
+        w.bool(true);
+        w.u16(0xABCD);
+        w.u32(0xDEADBEEF);
+        w.bytes(&[1, 2, 3]);
+
+        let mut r = StateReader::new(&w.buf);
+        assert_eq!(r.u8().unwrap(), 0x42);
+        assert!(r.bool().unwrap());
+        assert_eq!(r.u16().unwrap(), 0xABCD);
+        assert_eq!(r.u32().unwrap(), 0xDEADBEEF);
+        assert_eq!(r.bytes().unwrap(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_truncated_data_is_an_error_not_a_panic() {
+        let mut r = StateReader::new(&[0x01]);
+        assert!(r.u32().is_err());
+    }
+
+    #[test]
+    fn test_corrupt_length_prefix_does_not_overflow_or_panic() {
+        // A length word claiming to be (close to) usize::MAX must not panic
+        // via `pos + len` overflowing; it should surface as a normal error.
+        let mut data = Vec::new();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        let mut r = StateReader::new(&data);
+        assert!(r.bytes().is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Timer subsystem (DIV/TIMA/TMA/TAC)
+//!
+//! DIV increments every 256 T-cycles and resets to 0 on any write. TIMA
+//! increments at a rate selected by TAC and, on overflow, reloads from TMA
+//! and raises the timer interrupt.
+
+use crate::savestate::{StateReader, StateWriter};
+
+/// T-cycles between DIV increments (16384 Hz)
+const DIV_PERIOD: u32 = 256;
+
+pub struct Timer {
+    div: u8,
+    div_counter: u32,
+    tima: u8,
+    tima_counter: u32,
+    tma: u8,
+    tac: u8,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Timer {
+            div: 0,
+            div_counter: 0,
+            tima: 0,
+            tima_counter: 0,
+            tma: 0,
+            tac: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn div(&self) -> u8 {
+        self.div
+    }
+
+    /// Any write to DIV resets it (and its internal sub-counter) to 0.
+    pub fn reset_div(&mut self) {
+        self.div = 0;
+        self.div_counter = 0;
+    }
+
+    pub fn tima(&self) -> u8 {
+        self.tima
+    }
+
+    pub fn set_tima(&mut self, value: u8) {
+        self.tima = value;
+    }
+
+    pub fn tma(&self) -> u8 {
+        self.tma
+    }
+
+    pub fn set_tma(&mut self, value: u8) {
+        self.tma = value;
+    }
+
+    pub fn tac(&self) -> u8 {
+        self.tac
+    }
+
+    pub fn set_tac(&mut self, value: u8) {
+        self.tac = value & 0x07;
+    }
+
+    fn enabled(&self) -> bool {
+        self.tac & 0x04 != 0
+    }
+
+    /// TAC's 4 selectable input-clock rates, in T-cycles per TIMA increment.
+    fn tima_period(&self) -> u32 {
+        match self.tac & 0x03 {
+            0 => 1024, // 4096 Hz
+            1 => 16,   // 262144 Hz
+            2 => 64,   // 65536 Hz
+            3 => 256,  // 16384 Hz
+            _ => unreachable!(),
+        }
+    }
+
+    /// Advance the timer by `cycles` T-cycles. Returns true if TIMA overflowed
+    /// this call, in which case it has already been reloaded from TMA and the
+    /// caller should raise the timer interrupt.
+    pub fn step(&mut self, cycles: u32) -> bool {
+        self.div_counter += cycles;
+        while self.div_counter >= DIV_PERIOD {
+            self.div_counter -= DIV_PERIOD;
+            self.div = self.div.wrapping_add(1);
+        }
+
+        if !self.enabled() {
+            return false;
+        }
+
+        let period = self.tima_period();
+        let mut overflowed = false;
+
+        self.tima_counter += cycles;
+        while self.tima_counter >= period {
+            self.tima_counter -= period;
+
+            let (result, overflow) = self.tima.overflowing_add(1);
+            if overflow {
+                self.tima = self.tma;
+                overflowed = true;
+            } else {
+                self.tima = result;
+            }
+        }
+
+        overflowed
+    }
+
+    /// Serialize the visible registers and internal sub-cycle counters, so a
+    /// restored timer ticks over at exactly the point it was saved.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.div);
+        w.u32(self.div_counter);
+        w.u8(self.tima);
+        w.u32(self.tima_counter);
+        w.u8(self.tma);
+        w.u8(self.tac);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.div = r.u8()?;
+        self.div_counter = r.u32()?;
+        self.tima = r.u8()?;
+        self.tima_counter = r.u32()?;
+        self.tma = r.u8()?;
+        self.tac = r.u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_div_increments_every_256_cycles() {
+        let mut timer = Timer::new();
+        timer.step(255);
+        assert_eq!(timer.div(), 0);
+        timer.step(1);
+        assert_eq!(timer.div(), 1);
+    }
+
+    #[test]
+    fn test_div_resets_on_write() {
+        let mut timer = Timer::new();
+        timer.step(256);
+        assert_eq!(timer.div(), 1);
+
+        timer.reset_div();
+        assert_eq!(timer.div(), 0);
+    }
+
+    #[test]
+    fn test_tima_overflow_reloads_from_tma_and_raises_interrupt() {
+        let mut timer = Timer::new();
+        timer.set_tac(0x05); // enabled, 262144 Hz (16 cycles/tick)
+        timer.set_tma(0x10);
+        timer.set_tima(0xFF);
+
+        let overflowed = timer.step(16);
+
+        assert!(overflowed);
+        assert_eq!(timer.tima(), 0x10);
+    }
+
+    #[test]
+    fn test_tima_disabled_does_not_tick() {
+        let mut timer = Timer::new();
+        timer.set_tac(0x01); // disabled (bit 2 clear), rate bits only
+        timer.step(1024);
+        assert_eq!(timer.tima(), 0);
+    }
+
+    #[test]
+    fn test_save_state_roundtrip() {
+        let mut timer = Timer::new();
+        timer.set_tac(0x05);
+        timer.set_tma(0x10);
+        timer.step(300);
+
+        let mut w = StateWriter::new();
+        timer.save_state(&mut w);
+
+        let mut restored = Timer::new();
+        let mut r = StateReader::new(&w.buf);
+        restored.load_state(&mut r).unwrap();
+
+        assert_eq!(restored.div(), timer.div());
+        assert_eq!(restored.tima(), timer.tima());
+        assert_eq!(restored.tac(), timer.tac());
+    }
+}